@@ -1,15 +1,38 @@
 extern crate termsize;
 
 use clap::Parser;
+use flate2::read::MultiGzDecoder;
+use regex::Regex;
 use std::fs::File;
+use std::io;
 use std::io::{stdout, BufRead, BufReader, ErrorKind::BrokenPipe, Result, Seek, SeekFrom, Write};
 use std::process::exit;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = "A cool program")]
 struct Args {
+    /// Path to the delimited file to read, "-" for stdin, or a path
+    /// ending in ".gz" (or starting with gzip magic bytes) for gzip input
     filename: String,
 
+    #[arg(short = 'd', long, default_value_t = '\t')]
+    delimiter: char,
+
+    /// Comma-separated list of column names or 1-based indices to output,
+    /// in the given order (e.g. "name,3,email")
+    #[arg(short = 'c', long)]
+    columns: Option<String>,
+
+    /// Comma-separated list of column names or 1-based indices to sort by,
+    /// each optionally suffixed with ":desc" (e.g. "price:desc,name")
+    #[arg(short = 's', long)]
+    sort: Option<String>,
+
+    /// Keep only rows matching "COLUMN OP VALUE" (OP is one of = != < <= > >= ~);
+    /// may be given multiple times, combined with AND
+    #[arg(short = 'f', long = "filter")]
+    filters: Vec<String>,
+
     #[arg(short = 'r', long, default_value_t = 25)]
     header_repeat: u16,
 
@@ -44,6 +67,30 @@ struct Column {
     max_length: u16,
 }
 
+#[derive(Debug)]
+struct SortKey {
+    index: usize,
+    descending: bool,
+}
+
+#[derive(Debug)]
+enum FilterOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Match,
+}
+
+struct Predicate {
+    index: usize,
+    op: FilterOp,
+    value: String,
+    regex: Option<Regex>,
+}
+
 impl Column {
     fn new(name: &str) -> Self {
         Column {
@@ -72,11 +119,30 @@ struct DelimitedFile<T: BufRead> {
     header_repeat: Option<u16>,
     max_value_length: Option<u16>,
     borders: bool,
+    delimiter: char,
+    // Raw --columns argument, resolved into output_order once read_headers()
+    // has populated self.cols.
+    columns_spec: Option<String>,
+    // Source column indices to emit, in output order. Defaults to every
+    // column in file order when --columns isn't given.
+    output_order: Vec<usize>,
+    // Raw --sort argument, resolved into sort_keys once self.cols is known.
+    sort_spec: Option<String>,
+    sort_keys: Vec<SortKey>,
+    // Raw --filter arguments, resolved into predicates once self.cols is
+    // known. Multiple predicates are combined with AND.
+    filter_specs: Vec<String>,
+    predicates: Vec<Predicate>,
+    // When the underlying reader can't be rewound (stdin, gzip streams), or
+    // when --sort needs every row present, each parsed data row is cached
+    // here during analyze_rows() so print_aligned_rows() can replay it
+    // instead of seeking.
+    row_cache: Option<Vec<Vec<String>>>,
 }
 
 impl<T> DelimitedFile<T>
 where
-    T: BufRead + Seek,
+    T: BufRead,
 {
     pub fn new(reader: T) -> Self {
         Self {
@@ -86,6 +152,14 @@ where
             header_repeat: None,
             max_value_length: None,
             borders: false,
+            delimiter: '\t',
+            columns_spec: None,
+            output_order: Vec::new(),
+            sort_spec: None,
+            sort_keys: Vec::new(),
+            filter_specs: Vec::new(),
+            predicates: Vec::new(),
+            row_cache: None,
         }
     }
 
@@ -101,59 +175,410 @@ where
         self.borders = borders;
     }
 
-    fn line_parse(l: &str) -> Vec<String> {
-        l.split('\t')
-            .map(|s| String::from(s.trim()))
-            .collect::<Vec<String>>()
+    fn set_delimiter(&mut self, delimiter: char) {
+        self.delimiter = delimiter;
     }
 
-    fn seek_to_data(&mut self) -> Result<()> {
-        let _ = self
-            .reader
-            .seek(SeekFrom::Start(self.header_bytes as u64))?;
+    fn set_columns_spec(&mut self, columns_spec: Option<String>) {
+        self.columns_spec = columns_spec;
+    }
+
+    // Resolve columns_spec into output_order now that self.cols is known.
+    // Each key is either a 1-based index or a column name; unknown keys and
+    // out-of-range indices are reported as errors.
+    fn resolve_columns(&mut self) -> Result<()> {
+        self.output_order = match &self.columns_spec {
+            None => (0..self.cols.len()).collect(),
+            Some(spec) => {
+                let mut order = Vec::new();
+                for key in spec.split(',') {
+                    let key = key.trim();
+                    if let Ok(index) = key.parse::<usize>() {
+                        if index == 0 || index > self.cols.len() {
+                            return Err(io::Error::new(
+                                io::ErrorKind::InvalidInput,
+                                format!("column index out of range: {key}"),
+                            ));
+                        }
+                        order.push(index - 1);
+                    } else if let Some(pos) = self.cols.iter().position(|c| c.name == key) {
+                        order.push(pos);
+                    } else {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            format!("unknown column: {key}"),
+                        ));
+                    }
+                }
+                order
+            }
+        };
+        Ok(())
+    }
+
+    // Enable row caching for readers that can't be rewound with seek_to_data(),
+    // and for sorting, which needs every row buffered.
+    fn set_buffered(&mut self, buffered: bool) {
+        self.row_cache = if buffered { Some(Vec::new()) } else { None };
+    }
+
+    fn set_sort_spec(&mut self, sort_spec: Option<String>) {
+        self.sort_spec = sort_spec;
+    }
+
+    // Resolve sort_spec into sort_keys now that self.cols is known. Each key
+    // is "name" or "index", optionally suffixed with ":desc".
+    fn resolve_sort(&mut self) -> Result<()> {
+        self.sort_keys = match &self.sort_spec {
+            None => Vec::new(),
+            Some(spec) => {
+                let mut keys = Vec::new();
+                for key in spec.split(',') {
+                    let (name, descending) = match key.trim().split_once(':') {
+                        Some((name, "desc")) => (name.trim(), true),
+                        Some((name, "asc")) => (name.trim(), false),
+                        Some((name, dir)) => {
+                            return Err(io::Error::new(
+                                io::ErrorKind::InvalidInput,
+                                format!("unknown sort direction: {dir} (for column {name})"),
+                            ))
+                        }
+                        None => (key.trim(), false),
+                    };
+                    let index = if let Ok(index) = name.parse::<usize>() {
+                        if index == 0 || index > self.cols.len() {
+                            return Err(io::Error::new(
+                                io::ErrorKind::InvalidInput,
+                                format!("sort index out of range: {name}"),
+                            ));
+                        }
+                        index - 1
+                    } else if let Some(pos) = self.cols.iter().position(|c| c.name == name) {
+                        pos
+                    } else {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            format!("unknown sort column: {name}"),
+                        ));
+                    };
+                    keys.push(SortKey { index, descending });
+                }
+                keys
+            }
+        };
+        Ok(())
+    }
+
+    // Compare two rows by sort_keys in order, breaking ties with later keys.
+    // Numeric columns compare as f64, treating unparseable/empty cells as
+    // the smallest value so they sort together; other columns compare as
+    // trimmed strings.
+    fn compare_rows(&self, a: &[String], b: &[String]) -> std::cmp::Ordering {
+        use std::cmp::Ordering;
+        for key in &self.sort_keys {
+            let col = &self.cols[key.index];
+            let av = a.get(key.index).map(String::as_str).unwrap_or("");
+            let bv = b.get(key.index).map(String::as_str).unwrap_or("");
+            let ordering = match col.kind {
+                Numeric => {
+                    let an = av.trim().parse::<f64>().unwrap_or(f64::NEG_INFINITY);
+                    let bn = bv.trim().parse::<f64>().unwrap_or(f64::NEG_INFINITY);
+                    an.partial_cmp(&bn).unwrap_or(Ordering::Equal)
+                }
+                Textual => av.trim().cmp(bv.trim()),
+            };
+            let ordering = if key.descending { ordering.reverse() } else { ordering };
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+        Ordering::Equal
+    }
+
+    // Sort the buffered rows in place by sort_keys. A no-op unless --sort
+    // was given, since sorting needs every row buffered up front.
+    fn sort_rows(&mut self) {
+        if self.sort_keys.is_empty() {
+            return;
+        }
+        if let Some(mut cache) = self.row_cache.take() {
+            cache.sort_by(|a, b| self.compare_rows(a, b));
+            self.row_cache = Some(cache);
+        }
+    }
+
+    fn set_filter_specs(&mut self, filter_specs: Vec<String>) {
+        self.filter_specs = filter_specs;
+    }
+
+    // Split "COLUMN OP VALUE" into its three parts by scanning for the
+    // leftmost operator, preferring two-character operators so "!=" and
+    // ">=" aren't mistaken for "=" or ">".
+    fn split_predicate(spec: &str) -> Result<(&str, &str, &str)> {
+        const OPS: [&str; 7] = ["!=", ">=", "<=", "=", "<", ">", "~"];
+        for i in 0..spec.len() {
+            if !spec.is_char_boundary(i) {
+                continue;
+            }
+            let rest = &spec[i..];
+            if let Some(op) = OPS.iter().find(|op| rest.starts_with(**op)) {
+                let column = spec[..i].trim();
+                let value = spec[i + op.len()..].trim();
+                if !column.is_empty() {
+                    return Ok((column, op, value));
+                }
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid filter expression: {spec}"),
+        ))
+    }
+
+    // Resolve filter_specs into predicates now that self.cols is known,
+    // compiling any "~" operand as a regex once rather than per row.
+    fn resolve_filters(&mut self) -> Result<()> {
+        let mut predicates = Vec::new();
+        for spec in &self.filter_specs {
+            let (column, op, value) = Self::split_predicate(spec)?;
+            let index = if let Ok(index) = column.parse::<usize>() {
+                if index == 0 || index > self.cols.len() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("filter index out of range: {column}"),
+                    ));
+                }
+                index - 1
+            } else if let Some(pos) = self.cols.iter().position(|c| c.name == column) {
+                pos
+            } else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("unknown filter column: {column}"),
+                ));
+            };
+            let op = match op {
+                "=" => FilterOp::Eq,
+                "!=" => FilterOp::Ne,
+                "<" => FilterOp::Lt,
+                "<=" => FilterOp::Le,
+                ">" => FilterOp::Gt,
+                ">=" => FilterOp::Ge,
+                "~" => FilterOp::Match,
+                _ => unreachable!("split_predicate only returns known operators"),
+            };
+            let regex = if matches!(op, FilterOp::Match) {
+                Some(Regex::new(value).map_err(|e| {
+                    io::Error::new(io::ErrorKind::InvalidInput, format!("invalid regex /{value}/: {e}"))
+                })?)
+            } else {
+                None
+            };
+            predicates.push(Predicate {
+                index,
+                op,
+                value: value.to_string(),
+                regex,
+            });
+        }
+        self.predicates = predicates;
         Ok(())
     }
 
+    fn predicate_matches(&self, predicate: &Predicate, line: &[String]) -> bool {
+        let cell = line.get(predicate.index).map(String::as_str).unwrap_or("");
+        if let FilterOp::Match = predicate.op {
+            return predicate.regex.as_ref().unwrap().is_match(cell);
+        }
+
+        let numeric = matches!(self.cols[predicate.index].kind, Numeric);
+        if numeric {
+            if let (Ok(cell), Ok(value)) = (cell.trim().parse::<f64>(), predicate.value.trim().parse::<f64>()) {
+                return match predicate.op {
+                    FilterOp::Eq => cell == value,
+                    FilterOp::Ne => cell != value,
+                    FilterOp::Lt => cell < value,
+                    FilterOp::Le => cell <= value,
+                    FilterOp::Gt => cell > value,
+                    FilterOp::Ge => cell >= value,
+                    FilterOp::Match => unreachable!(),
+                };
+            }
+        }
+
+        let cell = cell.trim();
+        let value = predicate.value.trim();
+        match predicate.op {
+            FilterOp::Eq => cell == value,
+            FilterOp::Ne => cell != value,
+            FilterOp::Lt => cell < value,
+            FilterOp::Le => cell <= value,
+            FilterOp::Gt => cell > value,
+            FilterOp::Ge => cell >= value,
+            FilterOp::Match => unreachable!(),
+        }
+    }
+
+    // All predicates must match (implicit AND).
+    fn row_matches(&self, line: &[String]) -> bool {
+        self.predicates.iter().all(|p| self.predicate_matches(p, line))
+    }
+
+    // Split a single record into fields on self.delimiter, honoring RFC 4180
+    // quoting: a leading `"` opens a quoted field, `""` inside one is a
+    // literal quote, and the delimiter is literal while inside quotes.
+    // Unquoted fields are trimmed for backwards compatibility with plain
+    // whitespace-padded tabular input; quoted fields are taken verbatim.
+    fn parse_fields(&self, record: &str) -> Vec<String> {
+        let mut fields = Vec::new();
+        let mut field = String::new();
+        let mut in_quotes = false;
+        let mut quoted = false;
+        let mut chars = record.trim_end_matches(['\r', '\n']).chars().peekable();
+
+        while let Some(c) = chars.next() {
+            if in_quotes {
+                if c == '"' {
+                    if chars.peek() == Some(&'"') {
+                        field.push('"');
+                        chars.next();
+                    } else {
+                        in_quotes = false;
+                    }
+                } else {
+                    field.push(c);
+                }
+            } else if c == '"' && field.is_empty() {
+                in_quotes = true;
+                quoted = true;
+            } else if c == self.delimiter {
+                fields.push(if quoted { field.clone() } else { field.trim().to_string() });
+                field.clear();
+                quoted = false;
+            } else {
+                field.push(c);
+            }
+        }
+        fields.push(if quoted { field } else { field.trim().to_string() });
+        fields
+    }
+
+    // Mirrors parse_fields' field-start rule for what counts as a quote: a
+    // `"` only opens/closes a quoted field when it appears at the start of a
+    // field, so a literal `"` inside an unquoted field (e.g. 5ft6") doesn't
+    // throw off the balance.
+    fn ends_inside_quotes(&self, record: &str) -> bool {
+        let mut in_quotes = false;
+        let mut field_empty = true;
+        let mut chars = record.trim_end_matches(['\r', '\n']).chars().peekable();
+        while let Some(c) = chars.next() {
+            if in_quotes {
+                if c == '"' {
+                    if chars.peek() == Some(&'"') {
+                        chars.next();
+                    } else {
+                        in_quotes = false;
+                    }
+                }
+            } else if c == '"' && field_empty {
+                in_quotes = true;
+            } else if c == self.delimiter {
+                field_empty = true;
+                continue;
+            }
+            field_empty = false;
+        }
+        in_quotes
+    }
+
+    // Read one logical record, which may span multiple physical lines when a
+    // quoted field embeds a newline. Keeps consuming lines until every
+    // quoted field closes, per ends_inside_quotes().
+    fn read_record(&mut self) -> Result<Option<(String, usize)>> {
+        let mut record = String::new();
+        loop {
+            let mut line = String::new();
+            let bytes = self.reader.read_line(&mut line)?;
+            if bytes == 0 {
+                if record.is_empty() {
+                    return Ok(None);
+                }
+                let len = record.len();
+                return Ok(Some((record, len)));
+            }
+            record.push_str(&line);
+            if !self.ends_inside_quotes(&record) {
+                break;
+            }
+        }
+        let len = record.len();
+        Ok(Some((record, len)))
+    }
+
     // Read the header line of the file,
     // return the position after we finish
     // and the Vec of Columns
     fn read_headers(&mut self) -> Result<()> {
         let mut cols: Vec<Column> = Vec::new();
-        let mut headers = String::new();
-        self.header_bytes = self.reader.read_line(&mut headers)?;
-        let headers = Self::line_parse(&headers);
+        let (headers, bytes) = self.read_record()?.unwrap_or_default();
+        self.header_bytes = bytes;
+        let headers = self.parse_fields(&headers);
         cols.extend(headers.iter().map(|h| Column::new(h)));
         self.cols = cols;
-        Ok(())
+        self.resolve_columns()?;
+        self.resolve_sort()?;
+        self.resolve_filters()
     }
 
-    // Read the file, noting size and type of all the data
-    fn analyze_rows(&mut self) -> Result<()> {
-        let mut line_str = String::new();
-        while let Ok(bytes) = self.reader.read_line(&mut line_str) {
-            if bytes == 0 {
-                break;
+    // Columns that actually need type/width analysis: the output projection
+    // plus any sort or filter keys, since those may reference a column
+    // excluded from --columns.
+    fn analysis_indices(&self) -> Vec<usize> {
+        let mut indices = self.output_order.clone();
+        for key in &self.sort_keys {
+            if !indices.contains(&key.index) {
+                indices.push(key.index);
             }
-            let line = Self::line_parse(&line_str);
-            for (i, value) in line.iter().enumerate() {
-                if let Some(col) = self.cols.get_mut(i) {
+        }
+        for predicate in &self.predicates {
+            if !indices.contains(&predicate.index) {
+                indices.push(predicate.index);
+            }
+        }
+        indices
+    }
+
+    // Read the file, noting size and type of all the data. Only columns
+    // returned by analysis_indices() are analyzed, since those are the only
+    // ones that affect what gets printed or sorted. When row_cache is
+    // enabled, also stash each parsed row so we can replay it without
+    // rewinding the reader.
+    fn analyze_rows(&mut self) -> Result<()> {
+        let order = self.analysis_indices();
+        while let Some((record, _)) = self.read_record()? {
+            let line = self.parse_fields(&record);
+            for &idx in &order {
+                if let (Some(value), Some(col)) = (line.get(idx), self.cols.get_mut(idx)) {
                     col.update(value);
                 }
             }
-            line_str = String::new();
+            if let Some(cache) = &mut self.row_cache {
+                cache.push(line);
+            }
         }
         Ok(())
     }
 
     fn print_aligned_header(&mut self) -> Result<()> {
         let mut stdout = stdout().lock();
+        let cols: Vec<&Column> = self.output_order.iter().map(|&idx| &self.cols[idx]).collect();
         if self.borders {
-            for col in &self.cols {
+            for col in &cols {
                 write!(stdout, "{}┼", "─".repeat(self.print_length(col)))?;
             }
             writeln!(stdout)?;
         }
-        for col in &self.cols {
+        for col in &cols {
             write!(
                 stdout,
                 "{:-width$}",
@@ -163,7 +588,7 @@ where
         }
         if self.borders {
             writeln!(stdout)?;
-            for col in &self.cols {
+            for col in &cols {
                 write!(stdout, "{}┼", "─".repeat(self.print_length(col)))?;
             }
             writeln!(stdout)?;
@@ -192,50 +617,76 @@ where
         }
     }
 
+    fn print_projected_row(&self, stdout: &mut impl Write, line: &[String]) -> Result<()> {
+        for &idx in &self.output_order {
+            let value = line.get(idx).map(String::as_str).unwrap_or("");
+            let col = self.cols.get(idx).expect("No column for idx=");
+            write!(stdout, "{}", self.format_value(value, col))?;
+        }
+        writeln!(stdout)
+    }
+
     fn print_aligned_rows(&mut self) -> Result<()> {
-        let mut stdout = stdout().lock();
-        let mut line_str = String::new();
         let mut line_num = 0u16;
 
-        while let Ok(bytes) = self.reader.read_line(&mut line_str) {
-            if bytes == 0 {
-                break;
+        if let Some(cache) = self.row_cache.take() {
+            let mut stdout = stdout().lock();
+            for line in &cache {
+                if !self.row_matches(line) {
+                    continue;
+                }
+                line_num += 1;
+                if let Some(hr) = self.header_repeat {
+                    if line_num.is_multiple_of(hr) {
+                        self.print_aligned_header()?;
+                    }
+                }
+                self.print_projected_row(&mut stdout, line)?;
+            }
+            self.row_cache = Some(cache);
+            return Ok(());
+        }
+
+        let mut stdout = stdout().lock();
+        while let Some((record, _)) = self.read_record()? {
+            let line = self.parse_fields(&record);
+            if !self.row_matches(&line) {
+                continue;
             }
             line_num += 1;
             if let Some(hr) = self.header_repeat {
-                if line_num % hr == 0 {
+                if line_num.is_multiple_of(hr) {
                     self.print_aligned_header()?;
                 }
             }
 
-            let line = Self::line_parse(&line_str);
-            for (i, value) in line.iter().enumerate() {
-                let col = self.cols.get(i).expect("No column for i=");
-                write!(stdout, "{}", self.format_value(value, col))?;
-            }
-            writeln!(stdout)?;
-            line_str.truncate(0);
+            self.print_projected_row(&mut stdout, &line)?;
         }
         Ok(())
     }
 }
 
-fn main() -> Result<()> {
-    let args = Args::parse();
-    //let rows = match termsize::get() {
-    //    Some(size) => Some(size.rows),
-    //    None => Some(args.header_repeat),
-    //};
+impl<T> DelimitedFile<T>
+where
+    T: BufRead + Seek,
+{
+    fn seek_to_data(&mut self) -> Result<()> {
+        let _ = self
+            .reader
+            .seek(SeekFrom::Start(self.header_bytes as u64))?;
+        Ok(())
+    }
+}
 
-    let reader = match File::open(args.filename).map(BufReader::new) {
-        Ok(file) => file,
-        Err(err) => {
-            eprintln!("Could no open file: {err}");
-            exit(1);
-        }
-    };
+// Peek at the next two bytes without consuming them to detect the gzip
+// magic number (0x1f 0x8b), so we can transparently decompress ".gz" input
+// even when the extension is missing (e.g. piped through stdin).
+fn is_gzip<R: BufRead>(reader: &mut R) -> Result<bool> {
+    let peeked = reader.fill_buf()?;
+    Ok(peeked.len() >= 2 && peeked[0] == 0x1f && peeked[1] == 0x8b)
+}
 
-    let mut dfile = DelimitedFile::new(reader);
+fn configure<T: BufRead>(dfile: &mut DelimitedFile<T>, args: &Args, buffered: bool) {
     dfile.set_header_repeat(if args.no_header_repeat {
         None
     } else {
@@ -247,14 +698,37 @@ fn main() -> Result<()> {
         args.truncate_values
     });
     dfile.set_borders(args.borders);
+    dfile.set_delimiter(args.delimiter);
+    dfile.set_columns_spec(args.columns.clone());
+    dfile.set_sort_spec(args.sort.clone());
+    dfile.set_filter_specs(args.filters.clone());
+    dfile.set_buffered(buffered);
+}
+
+fn finish<T: BufRead>(mut dfile: DelimitedFile<T>) -> Result<()> {
     dfile.read_headers()?;
     dfile.analyze_rows()?;
-    dfile.seek_to_data()?;
+    dfile.sort_rows();
     dfile.print_aligned_header()?;
-    dfile.print_aligned_rows()?;
+    match dfile.print_aligned_rows() {
+        Ok(()) => Ok(()),
+        Err(err) => match err.kind() {
+            BrokenPipe => exit(0),
+            _ => {
+                eprintln!("Failed writing output: {}", err.kind());
+                exit(1);
+            }
+        },
+    }
+}
 
+fn finish_seekable<T: BufRead + Seek>(mut dfile: DelimitedFile<T>) -> Result<()> {
+    dfile.read_headers()?;
+    dfile.analyze_rows()?;
+    dfile.seek_to_data()?;
+    dfile.print_aligned_header()?;
     match dfile.print_aligned_rows() {
-        Ok(()) => (),
+        Ok(()) => Ok(()),
         Err(err) => match err.kind() {
             BrokenPipe => exit(0),
             _ => {
@@ -262,9 +736,53 @@ fn main() -> Result<()> {
                 exit(1);
             }
         },
-    };
+    }
+}
 
-    Ok(())
+fn main() -> Result<()> {
+    let args = Args::parse();
+    //let rows = match termsize::get() {
+    //    Some(size) => Some(size.rows),
+    //    None => Some(args.header_repeat),
+    //};
+
+    if args.filename == "-" {
+        let mut reader = BufReader::new(io::stdin());
+        let boxed: Box<dyn BufRead> = if is_gzip(&mut reader)? {
+            Box::new(BufReader::new(MultiGzDecoder::new(reader)))
+        } else {
+            Box::new(reader)
+        };
+        let mut dfile = DelimitedFile::new(boxed);
+        configure(&mut dfile, &args, true);
+        return finish(dfile);
+    }
+
+    let file = match File::open(&args.filename) {
+        Ok(file) => file,
+        Err(err) => {
+            eprintln!("Could no open file: {err}");
+            exit(1);
+        }
+    };
+    let mut reader = BufReader::new(file);
+    let is_gz = args.filename.ends_with(".gz") || is_gzip(&mut reader)?;
+
+    if is_gz {
+        let boxed: Box<dyn BufRead> = Box::new(BufReader::new(MultiGzDecoder::new(reader)));
+        let mut dfile = DelimitedFile::new(boxed);
+        configure(&mut dfile, &args, true);
+        finish(dfile)
+    } else {
+        let mut dfile = DelimitedFile::new(reader);
+        let needs_buffer = args.sort.is_some();
+        configure(&mut dfile, &args, needs_buffer);
+        if needs_buffer {
+            finish(dfile)
+        } else {
+            finish_seekable(dfile)
+        }
+    }
 }
 
 #[cfg(test)]
@@ -298,4 +816,144 @@ mod tests {
         dfile.set_max_value_length(Some(40));
         assert_eq!(dfile.print_length(&dfile.cols[0]), 7);
     }
+
+    #[test]
+    fn test_buffered_rows_replay_without_seek() {
+        use std::io::Cursor;
+
+        let buff = Cursor::new("a\tb\n1\t2\n3\t4\n");
+        let mut dfile = DelimitedFile::new(buff);
+        dfile.set_buffered(true);
+        dfile.read_headers().unwrap();
+        dfile.analyze_rows().unwrap();
+        assert_eq!(dfile.row_cache.as_ref().unwrap().len(), 2);
+        // No seek_to_data() call: the cached rows are replayed instead.
+        dfile.print_aligned_rows().unwrap();
+    }
+
+    #[test]
+    fn test_csv_quoting() {
+        use std::io::Cursor;
+
+        let buff = Cursor::new("a,b,c\n");
+        let mut dfile = DelimitedFile::new(buff);
+        dfile.set_delimiter(',');
+        assert_eq!(
+            dfile.parse_fields("1,\"hello, world\",\"she said \"\"hi\"\"\""),
+            vec!["1", "hello, world", "she said \"hi\""]
+        );
+    }
+
+    #[test]
+    fn test_csv_record_spans_embedded_newline() {
+        use std::io::Cursor;
+
+        let buff = Cursor::new("a,b\n1,\"line1\nline2\"\n3,4\n");
+        let mut dfile = DelimitedFile::new(buff);
+        dfile.set_delimiter(',');
+        dfile.read_headers().unwrap();
+        let (record, _) = dfile.read_record().unwrap().unwrap();
+        assert_eq!(dfile.parse_fields(&record), vec!["1", "line1\nline2"]);
+        let (record, _) = dfile.read_record().unwrap().unwrap();
+        assert_eq!(dfile.parse_fields(&record), vec!["3", "4"]);
+    }
+
+    #[test]
+    fn test_unquoted_literal_quote_does_not_join_following_lines() {
+        use std::io::Cursor;
+
+        let buff = Cursor::new("name\theight\nalice\t5ft6\"\nbob\t6ft1\ncarol\t5ft9\n");
+        let mut dfile = DelimitedFile::new(buff);
+        dfile.read_headers().unwrap();
+        let (record, _) = dfile.read_record().unwrap().unwrap();
+        assert_eq!(dfile.parse_fields(&record), vec!["alice", "5ft6\""]);
+        let (record, _) = dfile.read_record().unwrap().unwrap();
+        assert_eq!(dfile.parse_fields(&record), vec!["bob", "6ft1"]);
+        let (record, _) = dfile.read_record().unwrap().unwrap();
+        assert_eq!(dfile.parse_fields(&record), vec!["carol", "5ft9"]);
+    }
+
+    #[test]
+    fn test_columns_select_and_reorder_by_name_or_index() {
+        use std::io::Cursor;
+
+        let buff = Cursor::new("name\tage\temail\n");
+        let mut dfile = DelimitedFile::new(buff);
+        dfile.set_columns_spec(Some("email,1".to_string()));
+        dfile.read_headers().unwrap();
+        assert_eq!(dfile.output_order, vec![2, 0]);
+    }
+
+    #[test]
+    fn test_columns_unknown_name_is_an_error() {
+        use std::io::Cursor;
+
+        let buff = Cursor::new("name\tage\n");
+        let mut dfile = DelimitedFile::new(buff);
+        dfile.set_columns_spec(Some("nope".to_string()));
+        assert!(dfile.read_headers().is_err());
+    }
+
+    #[test]
+    fn test_sort_numeric_descending_then_name_ascending() {
+        use std::io::Cursor;
+
+        let buff = Cursor::new("name\tprice\nwidget\t10\ngadget\t30\ngizmo\t30\n");
+        let mut dfile = DelimitedFile::new(buff);
+        dfile.set_buffered(true);
+        dfile.set_sort_spec(Some("price:desc,name".to_string()));
+        dfile.read_headers().unwrap();
+        dfile.analyze_rows().unwrap();
+        dfile.sort_rows();
+        let names: Vec<&str> = dfile
+            .row_cache
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|row| row[0].as_str())
+            .collect();
+        assert_eq!(names, vec!["gadget", "gizmo", "widget"]);
+    }
+
+    #[test]
+    fn test_filter_numeric_comparison() {
+        use std::io::Cursor;
+
+        let buff = Cursor::new("name\tprice\nwidget\t10\ngadget\t30\ngizmo\t30\n");
+        let mut dfile = DelimitedFile::new(buff);
+        dfile.set_buffered(true);
+        dfile.set_filter_specs(vec!["price>=30".to_string()]);
+        dfile.read_headers().unwrap();
+        dfile.analyze_rows().unwrap();
+        let names: Vec<&str> = dfile
+            .row_cache
+            .as_ref()
+            .unwrap()
+            .iter()
+            .filter(|row| dfile.row_matches(row.as_slice()))
+            .map(|row| row[0].as_str())
+            .collect();
+        assert_eq!(names, vec!["gadget", "gizmo"]);
+    }
+
+    #[test]
+    fn test_filter_regex_match_is_combined_with_and() {
+        use std::io::Cursor;
+
+        let buff = Cursor::new("name\tprice\nwidget\t10\ngadget\t30\ngizmo\t30\n");
+        let mut dfile = DelimitedFile::new(buff);
+        dfile.set_buffered(true);
+        dfile.set_filter_specs(vec!["name~^g".to_string(), "price!=30".to_string()]);
+        dfile.read_headers().unwrap();
+        dfile.analyze_rows().unwrap();
+        let names: Vec<&str> = dfile
+            .row_cache
+            .as_ref()
+            .unwrap()
+            .iter()
+            .filter(|row| dfile.row_matches(row.as_slice()))
+            .map(|row| row[0].as_str())
+            .collect();
+        assert!(names.is_empty());
+    }
 }